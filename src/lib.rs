@@ -1,5 +1,6 @@
 extern crate libc;
 
+use std::collections::HashSet;
 use std::fs;
 use std::env;
 use std::fmt;
@@ -7,6 +8,9 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::io::Result;
 
+/// Maximum number of symlinks followed before giving up, matching the kernel's `ELOOP` behavior.
+const MAX_SYMLINK_DEPTH: usize = 40;
+
 pub enum SymlinkPath {
     Symlink {
         source: PathBuf,
@@ -14,6 +18,9 @@ pub enum SymlinkPath {
         suffix: PathBuf,
     },
     NotLink (PathBuf),
+    /// A symlink chain that loops back onto a link already visited, or that exceeds
+    /// `MAX_SYMLINK_DEPTH`. Carries the source path where the loop was detected.
+    Loop (PathBuf),
 }
 use SymlinkPath::*;
 
@@ -21,6 +28,7 @@ impl SymlinkPath {
     fn resolve(&self) -> PathBuf {
         match self {
             NotLink(p) => p.clone(),
+            Loop(p) => p.clone(),
             Symlink{ source, target, suffix } => {
                 let mut resolved = source.as_path().parent().unwrap().to_path_buf();
                 resolved.push(target.as_path());
@@ -33,6 +41,28 @@ impl SymlinkPath {
     }
 }
 
+/// A stable identity for a symlink, used to detect resolution loops.
+///
+/// Prefers the `(st_dev, st_ino)` pair, which correctly identifies a symlink even across
+/// renames or bind mounts. Falls back to the canonicalized path on platforms without inode
+/// metadata.
+#[derive(PartialEq, Eq, Hash, Clone)]
+enum LinkIdentity {
+    Inode(u64, u64),
+    Path(PathBuf),
+}
+
+fn link_identity(path: &Path) -> LinkIdentity {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let Ok(meta) = fs::symlink_metadata(path) {
+            return LinkIdentity::Inode(meta.dev(), meta.ino());
+        }
+    }
+    LinkIdentity::Path(fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()))
+}
+
 
 
 // # Formatting
@@ -66,10 +96,94 @@ impl fmt::Display for SymlinkPath {
                 format_symlink(f, path, None, path.metadata().is_ok()),
             Symlink { source, target: _, suffix } =>
                 format_symlink(f, source, Some(suffix), true),
+            Loop (source) =>
+                write!(f, "{} (cycle detected)", source.display()),
+        }
+    }
+}
+
+
+// # Structured output
+
+/// A structured, serializable view of a single step in a symlink resolution chain.
+///
+/// Unlike the `Display` impl, which is tailored to a colored terminal chain, this exposes
+/// every field (including `target`, which `Display` never shows) so that `--format json` can
+/// be consumed programmatically by scripts and editors.
+pub struct SymlinkRecord {
+    pub source: PathBuf,
+    pub target: Option<PathBuf>,
+    pub suffix: Option<PathBuf>,
+    pub resolved: PathBuf,
+    pub kind: &'static str,
+    pub exists: bool,
+}
+
+impl<'a> From<&'a SymlinkPath> for SymlinkRecord {
+    fn from(step: &'a SymlinkPath) -> SymlinkRecord {
+        match step {
+            NotLink (path) => SymlinkRecord {
+                source: path.clone(),
+                target: None,
+                suffix: None,
+                resolved: path.clone(),
+                kind: "notlink",
+                exists: path.metadata().is_ok(),
+            },
+            Symlink { source, target, suffix } => SymlinkRecord {
+                source: source.clone(),
+                target: Some(target.clone()),
+                suffix: Some(suffix.clone()),
+                resolved: step.resolve(),
+                kind: "symlink",
+                exists: true,
+            },
+            Loop (source) => SymlinkRecord {
+                source: source.clone(),
+                target: None,
+                suffix: None,
+                resolved: source.clone(),
+                kind: "loop",
+                exists: true,
+            },
         }
     }
 }
 
+impl SymlinkRecord {
+    /// Serialize this record as a single JSON object.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"source\":{},\"target\":{},\"suffix\":{},\"resolved\":{},\"kind\":\"{}\",\"exists\":{}}}",
+            json_string(&self.source.to_string_lossy()),
+            self.target.as_ref().map_or("null".to_owned(), |t| json_string(&t.to_string_lossy())),
+            self.suffix.as_ref().map_or("null".to_owned(), |s| json_string(&s.to_string_lossy())),
+            json_string(&self.resolved.to_string_lossy()),
+            self.kind,
+            self.exists,
+        )
+    }
+}
+
+/// Minimal JSON string escaping, sufficient for filesystem paths.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
 
 /// Query the symbolic target of a `path`.
 ///
@@ -107,6 +221,8 @@ fn find_symlink<P: AsRef<Path>>(path: P) -> Result<SymlinkPath>{
 pub struct ReadlinksIterator {
     path: PathBuf,
     done: bool,
+    seen: HashSet<LinkIdentity>,
+    depth: usize,
 }
 
 /// Readlinks path lookup logic
@@ -121,8 +237,14 @@ impl Iterator for ReadlinksIterator {
         }
         match find_symlink(&self.path) {
             Ok(symlink_path) => {
-                // When resolution is finished, we still need one iteration to print the final result
-                if let NotLink(_) = symlink_path {
+                if let Symlink { ref source, .. } = symlink_path {
+                    self.depth += 1;
+                    if !self.seen.insert(link_identity(source)) || self.depth > MAX_SYMLINK_DEPTH {
+                        self.done = true;
+                        return Some(Loop(source.clone()));
+                    }
+                } else {
+                    // When resolution is finished, we still need one iteration to print the final result
                     self.done = true;
                 }
                 self.path = symlink_path.resolve();
@@ -141,11 +263,87 @@ impl Iterator for ReadlinksIterator {
     }
 }
 
+impl ReadlinksIterator {
+    /// Consume the chain and return only its final, fully resolved element.
+    ///
+    /// When the final target exists, its path is further resolved with `fs::canonicalize` to
+    /// collapse any remaining `.`/`..` components, mirroring `readlink -f`.
+    pub fn final_target(mut self) -> SymlinkPath {
+        let mut last = NotLink(self.path.clone());
+        while let Some(step) = self.next() {
+            last = step;
+        }
+        match last {
+            NotLink(p) => NotLink(fs::canonicalize(&p).unwrap_or(p)),
+            other => other,
+        }
+    }
+}
+
 /// List all the intermediate symlinks in the resolution of `path`.
 pub fn resolve<P:AsRef<Path>>(path: P) -> ReadlinksIterator {
-    ReadlinksIterator { path: path.as_ref().to_path_buf(), done: false }
+    ReadlinksIterator { path: path.as_ref().to_path_buf(), done: false, seen: HashSet::new(), depth: 0 }
+}
+
+
+/// Candidate files to try for `exe` inside a single `$PATH` directory.
+///
+/// On Unix there is exactly one candidate: the exe joined to the directory. On Windows, there
+/// is one candidate per extension listed in `$PATHEXT`, in order, each appended to the exe
+/// name; the bare name without an extension is not tried.
+#[cfg(unix)]
+fn candidates(prefix: &Path, exe: &Path) -> Vec<PathBuf> {
+    vec![prefix.join(exe)]
 }
 
+#[cfg(windows)]
+fn candidates(prefix: &Path, exe: &Path) -> Vec<PathBuf> {
+    pathext().iter()
+        .map(|ext| prefix.join(format!("{}{}", exe.display(), ext)))
+        .collect()
+}
+
+/// The extensions Windows considers executable, from `$PATHEXT` (falling back to the
+/// cmd.exe default if unset), e.g. `.COM`, `.EXE`, `.BAT`, `.CMD`.
+#[cfg(windows)]
+fn pathext() -> Vec<String> {
+    env::var("PATHEXT")
+        .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string())
+        .split(';')
+        .map(|ext| ext.to_owned())
+        .collect()
+}
+
+/// Check that `bin` is a regular file this user is actually allowed to execute.
+///
+/// On Unix this looks at the owner/group/other execute bits, picking the right one
+/// depending on whether the current user owns the file or belongs to its group. This only
+/// consults the caller's primary gid, not supplementary groups, so a binary executable solely
+/// via a supplementary group is (conservatively) reported as not executable.
+#[cfg(unix)]
+fn is_executable(bin: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match fs::metadata(bin) {
+        Ok(metadata) if metadata.is_file() => {
+            let mode = metadata.mode();
+            if unsafe { libc::getuid() } == metadata.uid() {
+                mode & 0o100 != 0
+            } else if unsafe { libc::getgid() } == metadata.gid() {
+                mode & 0o010 != 0
+            } else {
+                mode & 0o001 != 0
+            }
+        },
+        _ => false,
+    }
+}
+
+/// On Windows, a file is executable if it is a regular file whose extension is listed in
+/// `$PATHEXT` (the candidates produced by `candidates()` already guarantee this).
+#[cfg(windows)]
+fn is_executable(bin: &Path) -> bool {
+    bin.is_file()
+}
 
 /// Lookup the full path of a command available in $PATH, or return the input as-is.
 pub fn expand_path<P: AsRef<Path>>(path: P) -> PathBuf {
@@ -156,9 +354,89 @@ pub fn expand_path<P: AsRef<Path>>(path: P) -> PathBuf {
     .and_then(|exe|
         env::var_os("PATH").and_then(|ref paths|
             env::split_paths(paths)
-            .map(|prefix| prefix.join(exe))
-            .find(|bin| bin.is_file()) // TODO: Check that it is actually executable.
+            .flat_map(|prefix| candidates(&prefix, exe))
+            .find(|bin| is_executable(bin))
         ))
     .unwrap_or_else(|| path.to_path_buf())
 }
 
+
+// # Auditing
+
+/// A concern raised by `audit_chain` about one step of a resolution chain.
+pub struct AuditWarning {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+/// Inspect an already-resolved chain for insecure symlinks: links with an ancestor directory
+/// that is world-writable without the sticky bit, or owned by someone other than the current
+/// user or root, or a chain that escapes a supplied `root` boundary via `..` or an absolute
+/// target. The owner of the link itself is not checked.
+///
+/// This is opt-in (`--audit`) and purely advisory: it reports concerns alongside the normal
+/// chain output rather than refusing to resolve anything.
+pub fn audit_chain(steps: &[SymlinkPath], root: Option<&Path>) -> Vec<AuditWarning> {
+    let mut warnings = Vec::new();
+    let mut checked = HashSet::new();
+    for step in steps {
+        if let Symlink { source, target, .. } = step {
+            audit_parents(source, &mut checked, &mut warnings);
+            if let Some(root) = root {
+                if target.is_absolute() && !target.starts_with(root) {
+                    warnings.push(AuditWarning {
+                        path: source.clone(),
+                        message: format!("absolute target {} escapes root {}", target.display(), root.display()),
+                    });
+                }
+                let resolved = step.resolve();
+                let resolved = fs::canonicalize(&resolved).unwrap_or(resolved);
+                if !resolved.starts_with(root) {
+                    warnings.push(AuditWarning {
+                        path: source.clone(),
+                        message: format!("resolves to {}, which escapes root {}", resolved.display(), root.display()),
+                    });
+                }
+            }
+        }
+    }
+    warnings
+}
+
+/// Flag world-writable or not-owned-by-us-or-root ancestors of `source`.
+///
+/// `checked` accumulates ancestors already looked at across the whole chain, so a directory
+/// shared by several symlinks in the chain is only warned about once.
+#[cfg(unix)]
+fn audit_parents(source: &Path, checked: &mut HashSet<PathBuf>, warnings: &mut Vec<AuditWarning>) {
+    use std::os::unix::fs::MetadataExt;
+    let uid = unsafe { libc::getuid() };
+    for ancestor in source.ancestors().skip(1) {
+        if !checked.insert(ancestor.to_path_buf()) {
+            continue;
+        }
+        if let Ok(metadata) = fs::symlink_metadata(ancestor) {
+            let mode = metadata.mode();
+            // Sticky world-writable directories (e.g. /tmp, mode 1777) only allow the owner
+            // of a file to remove or rename it, so they don't enable the usual symlink-swap
+            // attack and are not worth warning about.
+            if mode & 0o002 != 0 && mode & 0o1000 == 0 {
+                warnings.push(AuditWarning {
+                    path: ancestor.to_path_buf(),
+                    message: "world-writable directory".to_owned(),
+                });
+            }
+            // Root is trusted the same way the kernel trusts it, not just the invoking user.
+            if metadata.uid() != uid && metadata.uid() != 0 {
+                warnings.push(AuditWarning {
+                    path: ancestor.to_path_buf(),
+                    message: format!("owned by untrusted uid {}", metadata.uid()),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn audit_parents(_source: &Path, _checked: &mut HashSet<PathBuf>, _warnings: &mut Vec<AuditWarning>) {}
+
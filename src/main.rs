@@ -1,8 +1,11 @@
 extern crate clap;
 extern crate readlinks;
 
+use std::path::Path;
+
 use clap::{App, Arg};
 use readlinks::*;
+use readlinks::SymlinkPath::*;
 
 fn main() {
     let args = App::new("readlinks")
@@ -12,12 +15,79 @@ fn main() {
         .arg(Arg::with_name("path")
             .help("An executable reachable through $PATH, or a filesystem path.")
             .value_name("executable|path")
+            .multiple(true)
             .required(true)
         )
+        .arg(Arg::with_name("canonicalize")
+            .short("f")
+            .long("canonicalize")
+            .help("Only print the final, fully resolved path for each input.")
+        )
+        .arg(Arg::with_name("no-newline")
+            .short("n")
+            .long("no-newline")
+            .help("Do not print the trailing newline (only honored for a single path).")
+        )
+        .arg(Arg::with_name("format")
+            .long("format")
+            .takes_value(true)
+            .possible_values(&["text", "json"])
+            .default_value("text")
+            .help("Output format for the resolution chain.")
+        )
+        .arg(Arg::with_name("audit")
+            .long("audit")
+            .help("Warn about world-writable directories, untrusted owners, or links escaping --root.")
+        )
+        .arg(Arg::with_name("root")
+            .long("root")
+            .takes_value(true)
+            .value_name("dir")
+            .requires("audit")
+            .help("Boundary directory that --audit checks the chain does not escape.")
+        )
         .get_matches();
 
-    let path = expand_path(args.value_of("path").unwrap());
-    resolve(path).for_each(|s| {
-        println!("{}", s);
-    });
+    let paths: Vec<&str> = args.values_of("path").unwrap().collect();
+    let canonicalize = args.is_present("canonicalize");
+    let no_newline = args.is_present("no-newline") && paths.len() == 1;
+    let json = args.value_of("format") == Some("json");
+    let audit = args.is_present("audit");
+    let root = args.value_of("root").map(Path::new);
+
+    for path in paths {
+        let path = expand_path(path);
+        if canonicalize {
+            let target = match resolve(path).final_target() {
+                NotLink(p) => p,
+                Loop(p) => p,
+                Symlink { source, .. } => source,
+            };
+            print(&target.display(), no_newline);
+        } else if json {
+            let records: Vec<String> = resolve(path)
+                .map(|s| SymlinkRecord::from(&s).to_json())
+                .collect();
+            print(&format!("[{}]", records.join(",")), no_newline);
+        } else {
+            let steps: Vec<SymlinkPath> = resolve(path).collect();
+            let last = steps.len().saturating_sub(1);
+            for (i, step) in steps.iter().enumerate() {
+                print(step, no_newline && i == last);
+            }
+            if audit {
+                for warning in audit_chain(&steps, root) {
+                    eprintln!("warning: {}: {}", warning.path.display(), warning.message);
+                }
+            }
+        }
+    }
+}
+
+fn print<T: std::fmt::Display>(value: &T, no_newline: bool) {
+    if no_newline {
+        print!("{}", value);
+    } else {
+        println!("{}", value);
+    }
 }